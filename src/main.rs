@@ -23,18 +23,655 @@ const FEES_COLLECTED: [u8; 32] = [2; 32];
 const HOPS_REMAINING: [u8; 32] = [3; 32];
 const ROUTER_ADDRESS: [u8; 32] = [4; 32];
 const NONCE: [u8; 32] = [5; 32];
+const ACCESS_JOURNAL_LEN: [u8; 32] = [6; 32];
+const CHAIN_ID: [u8; 32] = [7; 32];
+
+// owner-configurable routing parameters, unset (zero) until configure()
+// is called - route()/handle_cell() fall back to the compile-time
+// constants below when unset, and pass the result to deploy_cell()
+const CFG_FEE: [u8; 32] = [8; 32];
+const CFG_HOPS: [u8; 32] = [9; 32];
+const CFG_GAS_DEPLOY: [u8; 32] = [10; 32];
+const CFG_GAS_FORWARD: [u8; 32] = [11; 32];
+const CFG_GAS_PER_CELL: [u8; 32] = [12; 32];
 
 // constants
 const ROUTING_FEE: u128 = 100_000_000_000_000; // 0.1 KSM
+const MIN_ROUTING_FEE: u128 = 1_000_000_000_000; // 0.001 KSM floor for configure()
 const HOP_COUNT: u8 = 12;
 const DEPLOYMENT_GAS: u64 = 500_000;
 const FORWARD_GAS: u64 = 100_000;
 const MAX_GAS_PER_CELL: u64 = 1_000_000;
 
+// EIP-2929-style warm/cold access costs: the *idea* (first touch is
+// expensive, later touches are cheap) is carried over from the EVM gas
+// schedule, but the literal 2600/100/2100 numbers are not - those are
+// EVM gas units, while every other gas constant in this file (and
+// api::gas_limit() itself) is in this chain's ref_time weight units.
+// scaled into that same unit space, at roughly the EVM schedule's
+// cold:warm ratios, so `required_hop_gas()` is comparing like with like.
+const COLD_ACCOUNT_COST: u64 = 50_000;
+const WARM_ACCOUNT_COST: u64 = 2_000;
+const COLD_SLOAD_COST: u64 = 40_000;
+const WARM_SLOAD_COST: u64 = 2_000;
+
+// tags distinguishing the two kinds of entries in the access set so an
+// account address and a storage key can never collide in the journal
+const ACCESS_TAG_ACCOUNT: u8 = 1;
+const ACCESS_TAG_STORAGE: u8 = 2;
+const ACCESS_TAG_JOURNAL: u8 = 0xfe;
+
+// --- EIP-2929-style access set -------------------------------------------
+//
+// warmth of an (address | storage key) is tracked as a storage flag at
+// `access_key(tag, data)`. every first-time flip from cold to warm is
+// journaled (append-only, indexed by ACCESS_JOURNAL_LEN) so that a failed
+// sub-call can roll the set back to the checkpoint taken before the call,
+// exactly like the EVM discards access-list warmth on a reverted sub-call.
+
+fn access_key(tag: u8, data: &[u8]) -> [u8; 32] {
+    let mut buf = [0u8; 64];
+    buf[0] = tag;
+    let n = data.len().min(buf.len() - 1);
+    buf[1..1 + n].copy_from_slice(&data[..n]);
+    let mut out = [0u8; 32];
+    api::hash_keccak_256(&buf[..1 + n], &mut out);
+    out
+}
+
+fn journal_slot(index: u32) -> [u8; 32] {
+    access_key(ACCESS_TAG_JOURNAL, &index.to_le_bytes())
+}
+
+fn journal_len() -> u32 {
+    let mut v = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &ACCESS_JOURNAL_LEN, &mut v);
+    u32::from_le_bytes(v[..4].try_into().unwrap())
+}
+
+fn set_journal_len(len: u32) {
+    let mut v = [0u8; 32];
+    v[..4].copy_from_slice(&len.to_le_bytes());
+    api::set_storage(uapi::StorageFlags::empty(), &ACCESS_JOURNAL_LEN, &v);
+}
+
+fn is_warm(key: &[u8; 32]) -> bool {
+    let mut v = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), key, &mut v);
+    v[0] != 0
+}
+
+// marks `key` warm, journaling the flip if this is its first touch.
+// returns true if it was already warm (caller pays the warm rate).
+fn mark_warm(key: &[u8; 32]) -> bool {
+    if is_warm(key) {
+        return true;
+    }
+    api::set_storage(uapi::StorageFlags::empty(), key, &[1u8; 32]);
+    let idx = journal_len();
+    api::set_storage(uapi::StorageFlags::empty(), &journal_slot(idx), key);
+    set_journal_len(idx + 1);
+    false
+}
+
+// checkpoint/rollback around a sub-call: if the sub-call errors, every
+// warmth flip made since the checkpoint is undone, matching EVM semantics
+// where a reverted call discards the access-list entries it created.
+fn access_checkpoint() -> u32 {
+    journal_len()
+}
+
+fn access_rollback(checkpoint: u32) {
+    let mut idx = journal_len();
+    while idx > checkpoint {
+        idx -= 1;
+        let slot = journal_slot(idx);
+        let mut key = [0u8; 32];
+        api::get_storage_or_zero(uapi::StorageFlags::empty(), &slot, &mut key);
+        api::set_storage(uapi::StorageFlags::empty(), &key, &[0u8; 32]);
+        api::set_storage(uapi::StorageFlags::empty(), &slot, &[0u8; 32]);
+    }
+    set_journal_len(checkpoint);
+}
+
+fn access_account(addr: &[u8; 20]) -> u64 {
+    let key = access_key(ACCESS_TAG_ACCOUNT, addr);
+    if mark_warm(&key) {
+        WARM_ACCOUNT_COST
+    } else {
+        COLD_ACCOUNT_COST
+    }
+}
+
+fn access_storage_key(skey: &[u8; 32]) -> u64 {
+    let key = access_key(ACCESS_TAG_STORAGE, skey);
+    if mark_warm(&key) {
+        WARM_SLOAD_COST
+    } else {
+        COLD_SLOAD_COST
+    }
+}
+
+// true cost of forwarding through `hops` cells to `destination`: each hop
+// pays to instantiate and forward into the next cell (`deployment_gas` +
+// `forward_gas` - the real opcode cost of the chain) *plus* the EIP-2929
+// access charge on top (a freshly instantiated contract is always a cold
+// account touch, and it then reads its own just-written HOPS_REMAINING
+// slot, always warm since the instantiate call itself already touched
+// it), followed by the final delivery to `destination` at whatever rate
+// the access set says it is.
+fn required_hop_gas(hops: u8, destination: &[u8; 20], deployment_gas: u64, forward_gas: u64) -> u64 {
+    let mut total: u64 = 0;
+    for _ in 0..hops {
+        total += deployment_gas + forward_gas + COLD_ACCOUNT_COST + WARM_SLOAD_COST;
+    }
+    let dest_key = access_key(ACCESS_TAG_ACCOUNT, destination);
+    total += if is_warm(&dest_key) {
+        WARM_ACCOUNT_COST
+    } else {
+        COLD_ACCOUNT_COST
+    };
+    total
+}
+
+// --- stealth-address delivery (alt_bn128, via EC precompiles) ------------
+//
+// lets the true recipient stay off the wire: the caller passes a view
+// point V and a spend point S (33-byte compressed points, tag || x - the
+// same encoding SEC1 uses for secp256k1, applied here to whichever curve
+// the EC precompiles actually implement), the router derives a one-time
+// delivery address from them, and every hop forwards to that derived
+// address exactly like it would a plain `destination` - only route()
+// needs to know stealth mode exists.
+//
+// precompiles 0x06/0x07 are the Ethereum alt_bn128 (BN254) add/mul
+// builtins, not a generic secp256k1 scalar-mul - there is no standard
+// secp256k1 EC precompile to call instead, so the curve used here is
+// alt_bn128 itself, matching what the builtins actually do.
+
+const PRECOMPILE_MODEXP: [u8; 20] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x05];
+const PRECOMPILE_EC_ADD: [u8; 20] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x06];
+const PRECOMPILE_EC_MUL: [u8; 20] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x07];
+
+// alt_bn128 field modulus, cube exponent and sqrt exponent (p mod 4 == 3,
+// so sqrt(a) = a^((p+1)/4) mod p), and the generator point (1, 2)
+const BN128_P: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c, 0xfd, 0x47,
+];
+const BN128_CUBE_EXP: [u8; 32] = {
+    let mut e = [0u8; 32];
+    e[31] = 3;
+    e
+};
+const BN128_SQRT_EXP: [u8; 32] = [
+    0x0c, 0x19, 0x13, 0x9c, 0xb8, 0x4c, 0x68, 0x0a, 0x6e, 0x14, 0x11, 0x6d, 0xa0, 0x60, 0x56, 0x17,
+    0x65, 0xe0, 0x5a, 0xa4, 0x5a, 0x1c, 0x72, 0xa3, 0x4f, 0x08, 0x23, 0x05, 0xb6, 0x1f, 0x3f, 0x52,
+];
+const BN128_GX: [u8; 32] = {
+    let mut x = [0u8; 32];
+    x[31] = 1;
+    x
+};
+const BN128_GY: [u8; 32] = {
+    let mut y = [0u8; 32];
+    y[31] = 2;
+    y
+};
+const BN128_CURVE_B: u8 = 3;
+
+fn bn128_generator() -> [u8; 64] {
+    let mut g = [0u8; 64];
+    g[..32].copy_from_slice(&BN128_GX);
+    g[32..].copy_from_slice(&BN128_GY);
+    g
+}
+
+// every EC/modexp operation goes through a fixed builtin address instead
+// of being hand-rolled in contract code, the same way the router already
+// treats `api::call` as the one true way to reach another contract
+fn call_precompile(addr: &[u8; 20], input: &[u8], output: &mut [u8]) {
+    let mut out_ref: &mut [u8] = output;
+    let result = api::call(
+        uapi::CallFlags::empty(),
+        addr,
+        api::gas_limit().min(MAX_GAS_PER_CELL),
+        0,
+        &[0xff; 32],
+        &[0u8; 32],
+        input,
+        Some(&mut out_ref),
+    );
+
+    if result.is_err() {
+        api::return_value(uapi::ReturnFlags::REVERT, b"precompile call failed");
+    }
+}
+
+fn modexp(base: &[u8; 32], exp: &[u8; 32], modulus: &[u8; 32]) -> [u8; 32] {
+    // builtin layout: three 32-byte big-endian lengths (all 32 here),
+    // then base, exponent and modulus
+    let mut input = [0u8; 192];
+    input[31] = 32;
+    input[63] = 32;
+    input[95] = 32;
+    input[96..128].copy_from_slice(base);
+    input[128..160].copy_from_slice(exp);
+    input[160..].copy_from_slice(modulus);
+    let mut out = [0u8; 32];
+    call_precompile(&PRECOMPILE_MODEXP, &input, &mut out);
+    out
+}
+
+fn ec_add(p: &[u8; 64], q: &[u8; 64]) -> [u8; 64] {
+    let mut input = [0u8; 128];
+    input[..64].copy_from_slice(p);
+    input[64..].copy_from_slice(q);
+    let mut out = [0u8; 64];
+    call_precompile(&PRECOMPILE_EC_ADD, &input, &mut out);
+    out
+}
+
+fn ec_mul(p: &[u8; 64], scalar: &[u8; 32]) -> [u8; 64] {
+    let mut input = [0u8; 96];
+    input[..64].copy_from_slice(p);
+    input[64..].copy_from_slice(scalar);
+    let mut out = [0u8; 64];
+    call_precompile(&PRECOMPILE_EC_MUL, &input, &mut out);
+    out
+}
+
+fn bytes_ge(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    for i in 0..32 {
+        if a[i] != b[i] {
+            return a[i] > b[i];
+        }
+    }
+    true
+}
+
+fn sub_mod_p(a: &[u8; 32]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let mut borrow: i16 = 0;
+    for i in (0..32).rev() {
+        let diff = a[i] as i16 - BN128_P[i] as i16 - borrow;
+        if diff < 0 {
+            out[i] = (diff + 256) as u8;
+            borrow = 1;
+        } else {
+            out[i] = diff as u8;
+            borrow = 0;
+        }
+    }
+    out
+}
+
+fn add_small_mod_p(a: &[u8; 32], delta: u8) -> [u8; 32] {
+    let mut out = *a;
+    let mut carry = delta as u16;
+    let mut i = 31;
+    loop {
+        let sum = out[i] as u16 + carry;
+        out[i] = (sum & 0xff) as u8;
+        carry = sum >> 8;
+        if carry == 0 || i == 0 {
+            break;
+        }
+        i -= 1;
+    }
+    if bytes_ge(&out, &BN128_P) {
+        out = sub_mod_p(&out);
+    }
+    out
+}
+
+// decompresses a 33-byte SEC1 pubkey (tag || x) into an uncompressed
+// (x, y) point: y = sqrt(x^3 + b) mod p, picking the root whose parity
+// matches the tag byte
+fn ec_decompress(pubkey: &[u8; 33]) -> [u8; 64] {
+    let mut x = [0u8; 32];
+    x.copy_from_slice(&pubkey[1..]);
+
+    let x_cubed = modexp(&x, &BN128_CUBE_EXP, &BN128_P);
+    let y_squared = add_small_mod_p(&x_cubed, BN128_CURVE_B);
+    let y = modexp(&y_squared, &BN128_SQRT_EXP, &BN128_P);
+
+    let wants_odd = pubkey[0] == 0x03;
+    let y = if (y[31] & 1 == 1) == wants_odd {
+        y
+    } else {
+        // the other root is p - y
+        let mut neg = [0u8; 32];
+        let mut borrow: i16 = 0;
+        for i in (0..32).rev() {
+            let diff = BN128_P[i] as i16 - y[i] as i16 - borrow;
+            borrow = if diff < 0 { 1 } else { 0 };
+            neg[i] = (diff + 256 * borrow) as u8;
+        }
+        neg
+    };
+
+    let mut point = [0u8; 64];
+    point[..32].copy_from_slice(&x);
+    point[32..].copy_from_slice(&y);
+    point
+}
+
+// derives the one-time delivery address P = S + keccak(r*V)*G and the
+// ephemeral point R = r*G that lets the recipient scan for it, per the
+// dual-key stealth address scheme. `router_nonce` must be unique per
+// route - reusing r links two payments together and defeats the whole
+// scheme, so this is seeded from the router's own globally-unique NONCE
+// rather than the caller-supplied intent nonce: two different callers
+// can share the same per-caller nonce, but never the same router nonce.
+fn derive_stealth_address(view_pubkey: &[u8; 33], spend_pubkey: &[u8; 33], router_nonce: u64) -> ([u8; 20], [u8; 64]) {
+    let view_point = ec_decompress(view_pubkey);
+    let spend_point = ec_decompress(spend_pubkey);
+
+    let mut r_seed = [0u8; 20];
+    r_seed[..8].copy_from_slice(&router_nonce.to_le_bytes());
+    r_seed[8..16].copy_from_slice(&api::ref_time_left().to_le_bytes());
+    let mut block_num = [0u8; 32];
+    api::block_number(&mut block_num);
+    r_seed[16..20].copy_from_slice(&block_num[..4]);
+    let mut r = [0u8; 32];
+    api::hash_keccak_256(&r_seed, &mut r);
+
+    let generator = bn128_generator();
+    let r_point = ec_mul(&generator, &r);
+
+    let shared_point = ec_mul(&view_point, &r);
+    let mut shared_secret = [0u8; 32];
+    api::hash_keccak_256(&shared_point, &mut shared_secret);
+
+    let shared_secret_point = ec_mul(&generator, &shared_secret);
+    let one_time_point = ec_add(&spend_point, &shared_secret_point);
+
+    let mut one_time_hash = [0u8; 32];
+    api::hash_keccak_256(&one_time_point, &mut one_time_hash);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&one_time_hash[12..]);
+
+    (address, r_point)
+}
+
+// --- observability events and stuck-funds recovery -----------------------
+//
+// `handle_cell()` used to swallow a failed final delivery silently, with
+// the funds just flowing to the router and no on-chain record. Events
+// surface every step of a route, and a failed delivery is credited to a
+// per-(sender, nonce) ledger the original sender can reclaim instead of
+// the funds being lost.
+
+const RECLAIM_SELECTOR: u32 = 0x80e9071b; // keccak("reclaim()")[..4]
+const CREDIT_STUCK_FUNDS_SELECTOR: u32 = 0x79e87acf; // keccak("creditStuckFunds(address,uint256,uint128)")[..4]
+const CONFIGURE_SELECTOR: u32 = 0x9b1c50c8; // keccak("configure(uint128,uint8,uint64,uint64,uint64)")[..4]
+
+fn emit_event(signature: &[u8], data: &[u8]) {
+    let mut topic0 = [0u8; 32];
+    api::hash_keccak_256(signature, &mut topic0);
+    api::deposit_event(&[topic0], data);
+}
+
+fn emit_route_started(first_cell: &[u8; 20]) {
+    let mut data = [0u8; 32];
+    data[12..32].copy_from_slice(first_cell);
+    emit_event(b"RouteStarted(address)", &data);
+}
+
+fn emit_hop_forwarded(hops_remaining: u8) {
+    let data = [hops_remaining];
+    emit_event(b"HopForwarded(uint8)", &data);
+}
+
+fn emit_delivered(dest: &[u8; 20], amount: u128) {
+    let mut data = [0u8; 48];
+    data[..20].copy_from_slice(dest);
+    data[20..36].copy_from_slice(&amount.to_le_bytes());
+    emit_event(b"Delivered(address,uint128)", &data[..36]);
+}
+
+fn emit_delivery_failed(dest: &[u8; 20], amount: u128) {
+    let mut data = [0u8; 48];
+    data[..20].copy_from_slice(dest);
+    data[20..36].copy_from_slice(&amount.to_le_bytes());
+    emit_event(b"DeliveryFailed(address,uint128)", &data[..36]);
+}
+
+fn stuck_funds_key(sender: &[u8; 20], nonce: u64) -> [u8; 32] {
+    let mut preimage = [0u8; 28];
+    preimage[..20].copy_from_slice(sender);
+    preimage[20..28].copy_from_slice(&nonce.to_le_bytes());
+    let mut key = [0u8; 32];
+    api::hash_keccak_256(&preimage, &mut key);
+    key
+}
+
+// calls back into the router's creditStuckFunds(sender, nonce, amount) -
+// the one place a terminating cell turns a failure into recoverable state
+// instead of a silent loss. shared by every hop-data-carrying failure path
+// in handle_cell(), not just final delivery.
+fn credit_stuck_funds_callback(router: &[u8; 20], sender: &[u8; 20], nonce: u64, amount: u128, gas: u64) {
+    let mut credit_call = [0u8; 48];
+    credit_call[..4].copy_from_slice(&CREDIT_STUCK_FUNDS_SELECTOR.to_be_bytes());
+    credit_call[4..24].copy_from_slice(sender);
+    credit_call[24..32].copy_from_slice(&nonce.to_le_bytes());
+    credit_call[32..48].copy_from_slice(&amount.to_le_bytes());
+    let _ = api::call(
+        uapi::CallFlags::empty(),
+        router,
+        gas,
+        0,
+        &[0xff; 32],
+        &[0u8; 32],
+        &credit_call,
+        None,
+    );
+}
+
+// called back by a terminating cell when its final delivery fails. every
+// cell self-replicates via api::own_code_hash() (deploy_cell() always
+// instantiates this same code), so verifying the caller runs identical
+// code to this router - instead of just assuming it, as the comment here
+// used to - is sufficient to reject an arbitrary external caller forging
+// a credit with no value ever having passed through the chain
+fn credit_stuck_funds() {
+    let mut caller = [0u8; 20];
+    api::caller(&mut caller);
+    let mut caller_code_hash = [0u8; 32];
+    api::code_hash(&caller, &mut caller_code_hash);
+    let mut own_hash = [0u8; 32];
+    api::own_code_hash(&mut own_hash);
+    if caller_code_hash != own_hash {
+        api::return_value(uapi::ReturnFlags::REVERT, b"not a router cell");
+    }
+
+    let mut sender = [0u8; 20];
+    api::call_data_copy(&mut sender, 4);
+    let mut nonce_bytes = [0u8; 8];
+    api::call_data_copy(&mut nonce_bytes, 24);
+    let nonce = u64::from_le_bytes(nonce_bytes);
+    let mut amount_bytes = [0u8; 16];
+    api::call_data_copy(&mut amount_bytes, 32);
+    let amount = u128::from_le_bytes(amount_bytes);
+
+    let key = stuck_funds_key(&sender, nonce);
+    let mut stored = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &key, &mut stored);
+    let total = u128::from_le_bytes(stored[..16].try_into().unwrap()).saturating_add(amount);
+    stored[..16].copy_from_slice(&total.to_le_bytes());
+    api::set_storage(uapi::StorageFlags::empty(), &key, &stored);
+}
+
+// lets the original sender of a route whose final delivery failed pull
+// their stuck balance back out, the same reentrancy-safe
+// zero-before-transfer pattern withdraw() already uses
+fn reclaim() {
+    let mut caller = [0u8; 20];
+    api::caller(&mut caller);
+    let mut nonce_bytes = [0u8; 8];
+    api::call_data_copy(&mut nonce_bytes, 4);
+    let nonce = u64::from_le_bytes(nonce_bytes);
+
+    let key = stuck_funds_key(&caller, nonce);
+    let mut stuck_storage = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &key, &mut stuck_storage);
+    let stuck = u128::from_le_bytes(stuck_storage[..16].try_into().unwrap());
+
+    if stuck == 0 {
+        api::return_value(uapi::ReturnFlags::REVERT, b"nothing to reclaim");
+    }
+
+    // reset before transfer (reentrancy protection)
+    api::set_storage(uapi::StorageFlags::empty(), &key, &[0u8; 32]);
+
+    let mut stuck_bytes = [0u8; 32];
+    stuck_bytes[..16].copy_from_slice(&stuck.to_le_bytes());
+
+    let result = api::call(
+        uapi::CallFlags::empty(),
+        &caller,
+        api::gas_limit().saturating_sub(FORWARD_GAS).min(MAX_GAS_PER_CELL),
+        0,
+        &[0xff; 32],
+        &stuck_bytes,
+        &[],
+        None,
+    );
+
+    if result.is_err() {
+        // restore the stuck balance if the transfer failed
+        api::set_storage(uapi::StorageFlags::empty(), &key, &stuck_storage);
+        api::return_value(uapi::ReturnFlags::REVERT, b"reclaim failed");
+    }
+
+    api::return_value(uapi::ReturnFlags::empty(), &stuck_bytes[..16]);
+}
+
+// shared owner check, originally inlined in withdraw() and now reused by
+// configure() too
+fn require_owner() -> [u8; 20] {
+    let mut caller = [0u8; 20];
+    api::caller(&mut caller);
+
+    let mut owner_storage = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &OWNER, &mut owner_storage);
+
+    if caller != owner_storage[..20] {
+        api::return_value(uapi::ReturnFlags::REVERT, b"not owner");
+    }
+
+    caller
+}
+
+// owner-configurable routing parameters, read by route() / handle_cell()
+// (and passed on to deploy_cell()) in place of the compile-time constants. each falls back to
+// its constant default until configure() has written a non-zero value.
+fn configured_fee() -> u128 {
+    let mut v = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &CFG_FEE, &mut v);
+    let stored = u128::from_le_bytes(v[..16].try_into().unwrap());
+    if stored == 0 { ROUTING_FEE } else { stored }
+}
+
+fn configured_hop_count() -> u8 {
+    let mut v = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &CFG_HOPS, &mut v);
+    if v[0] == 0 { HOP_COUNT } else { v[0] }
+}
+
+fn configured_deployment_gas() -> u64 {
+    let mut v = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &CFG_GAS_DEPLOY, &mut v);
+    let stored = u64::from_le_bytes(v[..8].try_into().unwrap());
+    if stored == 0 { DEPLOYMENT_GAS } else { stored }
+}
+
+fn configured_forward_gas() -> u64 {
+    let mut v = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &CFG_GAS_FORWARD, &mut v);
+    let stored = u64::from_le_bytes(v[..8].try_into().unwrap());
+    if stored == 0 { FORWARD_GAS } else { stored }
+}
+
+fn configured_max_gas_per_cell() -> u64 {
+    let mut v = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &CFG_GAS_PER_CELL, &mut v);
+    let stored = u64::from_le_bytes(v[..8].try_into().unwrap());
+    if stored == 0 { MAX_GAS_PER_CELL } else { stored }
+}
+
+// owner-only reconfiguration of the routing parameters that used to be
+// compile-time constants. every field is validated before anything is
+// written - a REVERT return already discards every storage write made in
+// this call's frame, so there's nothing left to roll back and nothing
+// can be left half-applied by the time the writes below run.
+fn configure() {
+    require_owner();
+
+    let mut fee_bytes = [0u8; 16];
+    api::call_data_copy(&mut fee_bytes, 4);
+    let new_fee = u128::from_le_bytes(fee_bytes);
+
+    let mut hops_byte = [0u8; 1];
+    api::call_data_copy(&mut hops_byte, 20);
+    let new_hops = hops_byte[0];
+
+    let mut gas_deploy_bytes = [0u8; 8];
+    api::call_data_copy(&mut gas_deploy_bytes, 21);
+    let new_gas_deploy = u64::from_le_bytes(gas_deploy_bytes);
+
+    let mut gas_forward_bytes = [0u8; 8];
+    api::call_data_copy(&mut gas_forward_bytes, 29);
+    let new_gas_forward = u64::from_le_bytes(gas_forward_bytes);
+
+    let mut gas_per_cell_bytes = [0u8; 8];
+    api::call_data_copy(&mut gas_per_cell_bytes, 37);
+    let new_gas_per_cell = u64::from_le_bytes(gas_per_cell_bytes);
+
+    if new_fee < MIN_ROUTING_FEE {
+        api::return_value(uapi::ReturnFlags::REVERT, b"fee too low");
+    }
+    if new_hops == 0 || new_hops > HOP_COUNT {
+        api::return_value(uapi::ReturnFlags::REVERT, b"hops out of range");
+    }
+    if new_gas_deploy == 0 {
+        api::return_value(uapi::ReturnFlags::REVERT, b"deployment gas out of range");
+    }
+    if new_gas_forward == 0 {
+        api::return_value(uapi::ReturnFlags::REVERT, b"forward gas out of range");
+    }
+    if new_gas_per_cell == 0 {
+        api::return_value(uapi::ReturnFlags::REVERT, b"per-cell gas out of range");
+    }
+
+    let mut fee_storage = [0u8; 32];
+    fee_storage[..16].copy_from_slice(&new_fee.to_le_bytes());
+    api::set_storage(uapi::StorageFlags::empty(), &CFG_FEE, &fee_storage);
+
+    let mut hops_storage = [0u8; 32];
+    hops_storage[0] = new_hops;
+    api::set_storage(uapi::StorageFlags::empty(), &CFG_HOPS, &hops_storage);
+
+    let mut gas_deploy_storage = [0u8; 32];
+    gas_deploy_storage[..8].copy_from_slice(&new_gas_deploy.to_le_bytes());
+    api::set_storage(uapi::StorageFlags::empty(), &CFG_GAS_DEPLOY, &gas_deploy_storage);
+
+    let mut gas_forward_storage = [0u8; 32];
+    gas_forward_storage[..8].copy_from_slice(&new_gas_forward.to_le_bytes());
+    api::set_storage(uapi::StorageFlags::empty(), &CFG_GAS_FORWARD, &gas_forward_storage);
+
+    let mut gas_per_cell_storage = [0u8; 32];
+    gas_per_cell_storage[..8].copy_from_slice(&new_gas_per_cell.to_le_bytes());
+    api::set_storage(uapi::StorageFlags::empty(), &CFG_GAS_PER_CELL, &gas_per_cell_storage);
+
+    api::return_value(uapi::ReturnFlags::empty(), &[]);
+}
+
 #[no_mangle]
 #[polkavm_derive::polkavm_export]
 pub extern "C" fn deploy() {
-    let mut input = [0u8; 32];
+    let mut input = [0u8; 46];
     api::call_data_copy(&mut input, 0);
     
     match input[0] {
@@ -43,34 +680,65 @@ pub extern "C" fn deploy() {
             let mut type_storage = [0u8; 32];
             type_storage[0] = TYPE_ROUTER;
             api::set_storage(uapi::StorageFlags::empty(), &CONTRACT_TYPE, &type_storage);
-            
+
             // store deployer as owner
             let mut caller = [0u8; 20];
             api::caller(&mut caller);
             let mut owner_storage = [0u8; 32];
             owner_storage[..20].copy_from_slice(&caller);
             api::set_storage(uapi::StorageFlags::empty(), &OWNER, &owner_storage);
-            
+
             // initialize state
             api::set_storage(uapi::StorageFlags::empty(), &FEES_COLLECTED, &[0u8; 32]);
             api::set_storage(uapi::StorageFlags::empty(), &NONCE, &[0u8; 32]);
+
+            // bind this router to the chain it was deployed on, so salts
+            // (and, below, route intents) can't be replayed across chains
+            let mut chain_id = [0u8; 32];
+            api::chain_id(&mut chain_id);
+            api::set_storage(uapi::StorageFlags::empty(), &CHAIN_ID, &chain_id);
         }
         TYPE_CELL => {
             // cell deployment
             let mut type_storage = [0u8; 32];
             type_storage[0] = TYPE_CELL;
             api::set_storage(uapi::StorageFlags::empty(), &CONTRACT_TYPE, &type_storage);
-            
+
             // store remaining hops with bounds check
             let hops = input[1].min(HOP_COUNT);
             let mut hops_storage = [0u8; 32];
             hops_storage[0] = hops;
             api::set_storage(uapi::StorageFlags::empty(), &HOPS_REMAINING, &hops_storage);
-            
+            // the constructor's own write warms the slot, so handle_cell()'s
+            // first read of it is charged as warm
+            mark_warm(&access_key(ACCESS_TAG_STORAGE, &HOPS_REMAINING));
+
             // store router address for gas refund
             let mut router_storage = [0u8; 32];
             router_storage[..20].copy_from_slice(&input[2..22]);
             api::set_storage(uapi::StorageFlags::empty(), &ROUTER_ADDRESS, &router_storage);
+
+            // cells fold the chain id into the next hop's salt too, so
+            // every contract needs its own copy, not just the router's
+            let mut chain_id = [0u8; 32];
+            api::chain_id(&mut chain_id);
+            api::set_storage(uapi::StorageFlags::empty(), &CHAIN_ID, &chain_id);
+
+            // the router resolves its own configured gas parameters once
+            // per route and carries them down through every cell's
+            // constructor, since a cell's own storage has no way to see
+            // the router's CFG_GAS_* slots directly
+            let mut gas_deploy_storage = [0u8; 32];
+            gas_deploy_storage[..8].copy_from_slice(&input[22..30]);
+            api::set_storage(uapi::StorageFlags::empty(), &CFG_GAS_DEPLOY, &gas_deploy_storage);
+
+            let mut gas_forward_storage = [0u8; 32];
+            gas_forward_storage[..8].copy_from_slice(&input[30..38]);
+            api::set_storage(uapi::StorageFlags::empty(), &CFG_GAS_FORWARD, &gas_forward_storage);
+
+            let mut gas_per_cell_storage = [0u8; 32];
+            gas_per_cell_storage[..8].copy_from_slice(&input[38..46]);
+            api::set_storage(uapi::StorageFlags::empty(), &CFG_GAS_PER_CELL, &gas_per_cell_storage);
         }
         _ => api::return_value(uapi::ReturnFlags::REVERT, b"invalid type"),
     }
@@ -96,60 +764,165 @@ fn handle_router() {
     match u32::from_be_bytes(selector) {
         0x12345678 => route(),
         0x3ccfd60b => withdraw(),
+        RECLAIM_SELECTOR => reclaim(),
+        CREDIT_STUCK_FUNDS_SELECTOR => credit_stuck_funds(),
+        CONFIGURE_SELECTOR => configure(),
         _ => api::return_value(uapi::ReturnFlags::REVERT, b"unknown selector"),
     }
 }
 
 fn route() {
+    // the access set is transaction-scoped (EVM warmth doesn't survive
+    // past the transaction that created it), but it's backed by real
+    // router storage that otherwise persists across calls - roll every
+    // journaled flip back to empty before this route touches anything,
+    // so warmth never leaks from one route() call into the next
+    access_rollback(0);
+
+    // read the client's route intent: an 8-byte nonce and 8-byte chain id
+    // (EIP-155 style domain separation), binding the intent to exactly one
+    // chain and one use so it can't be resubmitted
+    let mut nonce_bytes = [0u8; 8];
+    api::call_data_copy(&mut nonce_bytes, 4);
+    let client_nonce = u64::from_le_bytes(nonce_bytes);
+
+    let mut intent_chain_id = [0u8; 8];
+    api::call_data_copy(&mut intent_chain_id, 12);
+
+    let mut stored_chain_id = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &CHAIN_ID, &mut stored_chain_id);
+    // api::chain_id() returns a little-endian U256 (like value_transferred()
+    // and block_number()), so the chain id's actual entropy lives in [..8]
+    if intent_chain_id != stored_chain_id[..8] {
+        api::return_value(uapi::ReturnFlags::REVERT, b"wrong chain");
+    }
+
+    // reject unless the nonce strictly advances the caller's last-seen
+    // nonce - first use (stored nonce 0) accepts nonce >= 1
+    let mut caller = [0u8; 20];
+    api::caller(&mut caller);
+    let mut last_nonce_key = [0u8; 32];
+    api::hash_keccak_256(&caller, &mut last_nonce_key);
+    let mut last_nonce_storage = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &last_nonce_key, &mut last_nonce_storage);
+    let last_nonce = u64::from_le_bytes(last_nonce_storage[..8].try_into().unwrap());
+
+    if client_nonce <= last_nonce {
+        api::return_value(uapi::ReturnFlags::REVERT, b"replayed intent");
+    }
+
+    let mut new_last_nonce_storage = [0u8; 32];
+    new_last_nonce_storage[..8].copy_from_slice(&client_nonce.to_le_bytes());
+    api::set_storage(uapi::StorageFlags::empty(), &last_nonce_key, &new_last_nonce_storage);
+
+    // advance the router's own globally-unique nonce now, ahead of stealth
+    // derivation and cell deployment below, both of which need entropy
+    // that's unique per route regardless of which caller submitted it -
+    // client_nonce is only unique per-caller, so it can't serve that role
+    let mut nonce_storage = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &NONCE, &mut nonce_storage);
+    let nonce = u64::from_le_bytes(nonce_storage[..8].try_into().unwrap()).wrapping_add(1);
+    nonce_storage[..8].copy_from_slice(&nonce.to_le_bytes());
+    api::set_storage(uapi::StorageFlags::empty(), &NONCE, &nonce_storage);
+
     // read destination
     let mut destination = [0u8; 20];
-    api::call_data_copy(&mut destination, 4);
-    
+    api::call_data_copy(&mut destination, 20);
+
+    // optional caller-supplied access list: a count byte followed by that
+    // many 20-byte addresses the caller already knows will be touched.
+    // pre-warming them here means the pre-flight check below charges the
+    // cheap warm rate instead of reserving worst-case cold gas for them.
+    // this warmth stays in the router's access set: each cell keeps its
+    // own, so the final delivery in handle_cell() sees the destination cold.
+    let mut access_list_len = [0u8; 1];
+    api::call_data_copy(&mut access_list_len, 40);
+    for i in 0..access_list_len[0] as usize {
+        let mut addr = [0u8; 20];
+        api::call_data_copy(&mut addr, 41 + (i * 20) as u32);
+        access_account(&addr);
+    }
+    let access_list_end = 41 + access_list_len[0] as u32 * 20;
+
+    // optional stealth-address delivery mode: a mode byte, and if set, a
+    // 33-byte view pubkey followed by a 33-byte spend pubkey. `destination`
+    // is replaced with the derived one-time address before anything below
+    // ever sees it, so every hop forwards to the stealth address exactly
+    // like it would a cleartext one
+    let mut stealth_mode = [0u8; 1];
+    api::call_data_copy(&mut stealth_mode, access_list_end);
+    let mut ephemeral_point = [0u8; 64];
+    if stealth_mode[0] == 1 {
+        let mut view_pubkey = [0u8; 33];
+        api::call_data_copy(&mut view_pubkey, access_list_end + 1);
+        let mut spend_pubkey = [0u8; 33];
+        api::call_data_copy(&mut spend_pubkey, access_list_end + 34);
+
+        let (stealth_address, r_point) = derive_stealth_address(&view_pubkey, &spend_pubkey, nonce);
+        destination = stealth_address;
+        ephemeral_point = r_point;
+    }
+
+    // routing parameters the owner may have reconfigured away from their
+    // compile-time defaults via configure()
+    let fee = configured_fee();
+    let hop_count = configured_hop_count();
+    let deployment_gas = configured_deployment_gas();
+    let forward_gas = configured_forward_gas();
+    let max_gas_per_cell = configured_max_gas_per_cell();
+
     // check value includes fee
     let mut value_bytes = [0u8; 32];
     api::value_transferred(&mut value_bytes);
     let value = u128::from_le_bytes(value_bytes[..16].try_into().unwrap());
-    
-    if value <= ROUTING_FEE {
+
+    if value <= fee {
         api::return_value(uapi::ReturnFlags::REVERT, b"insufficient fee");
     }
-    
+
     // gas exhaustion attack protection: verify sufficient gas for full chain
     // prevents griefing where tx has fee but insufficient gas for 12 deployments
-    let required_gas = DEPLOYMENT_GAS + (HOP_COUNT as u64 * MAX_GAS_PER_CELL);
+    // EIP-2929 warm/cold accounting replaces the old flat per-cell estimate,
+    // which over-reserved and produced spurious "insufficient gas" reverts.
+    // required_hop_gas() already prices every hop's own instantiate+forward
+    // (including the first, deployed right below), so it isn't added again here
+    let required_gas = required_hop_gas(hop_count, &destination, deployment_gas, forward_gas);
     if api::gas_limit() < required_gas {
         api::return_value(uapi::ReturnFlags::REVERT, b"insufficient gas");
     }
-    
+
     // accumulate fees
+    access_storage_key(&FEES_COLLECTED);
     let mut fees_storage = [0u8; 32];
     api::get_storage_or_zero(uapi::StorageFlags::empty(), &FEES_COLLECTED, &mut fees_storage);
-    let total_fees = u128::from_le_bytes(fees_storage[..16].try_into().unwrap()).saturating_add(ROUTING_FEE);
+    let total_fees = u128::from_le_bytes(fees_storage[..16].try_into().unwrap()).saturating_add(fee);
     fees_storage[..16].copy_from_slice(&total_fees.to_le_bytes());
     api::set_storage(uapi::StorageFlags::empty(), &FEES_COLLECTED, &fees_storage);
-    
-    // increment nonce for salt entropy
-    // mitigates salt predictability in cell deployment
-    let mut nonce_storage = [0u8; 32];
-    api::get_storage_or_zero(uapi::StorageFlags::empty(), &NONCE, &mut nonce_storage);
-    let nonce = u64::from_le_bytes(nonce_storage[..8].try_into().unwrap()).wrapping_add(1);
-    nonce_storage[..8].copy_from_slice(&nonce.to_le_bytes());
-    api::set_storage(uapi::StorageFlags::empty(), &NONCE, &nonce_storage);
-    
+
     // get router address for cells
     let mut router_addr = [0u8; 20];
     api::address(&mut router_addr);
-    
-    // deploy first cell with 12 hops
-    let first_cell = deploy_cell(HOP_COUNT, router_addr, nonce);
-    
+
+    // deploy first cell with the configured hop depth
+    let first_cell = deploy_cell(hop_count, router_addr, nonce, deployment_gas, forward_gas, max_gas_per_cell);
+
     // forward funds minus fee
-    let forward_value = value - ROUTING_FEE;
+    let forward_value = value - fee;
     let mut forward_bytes = [0u8; 32];
     forward_bytes[..16].copy_from_slice(&forward_value.to_le_bytes());
-    
+
+    // every hop carries destination, original sender and intent nonce
+    // along with it unchanged, so the terminating cell can credit a
+    // failed delivery to the right STUCK_FUNDS entry
+    let mut hop_data = [0u8; 48];
+    hop_data[..20].copy_from_slice(&destination);
+    hop_data[20..40].copy_from_slice(&caller);
+    hop_data[40..48].copy_from_slice(&client_nonce.to_le_bytes());
+
     // call first cell with calculated gas allocation
-    let gas_for_cell = api::gas_limit().saturating_sub(DEPLOYMENT_GAS).min(MAX_GAS_PER_CELL * HOP_COUNT as u64);
+    let checkpoint = access_checkpoint();
+    access_account(&first_cell);
+    let gas_for_cell = api::gas_limit().saturating_sub(deployment_gas).min(max_gas_per_cell * hop_count as u64);
     let result = api::call(
         uapi::CallFlags::empty(),
         &first_cell,
@@ -157,61 +930,108 @@ fn route() {
         0,
         &[0xff; 32],
         &forward_bytes,
-        &destination[..],
+        &hop_data[..],
         None,
     );
-    
+
     if result.is_err() {
+        // the access set shouldn't remember touches made by a call that
+        // never actually went through
+        access_rollback(checkpoint);
         api::return_value(uapi::ReturnFlags::REVERT, b"routing failed");
     }
-    
-    // return first cell address
-    let mut response = [0u8; 32];
+
+    emit_route_started(&first_cell);
+
+    // return first cell address, plus the ephemeral point R (zero unless
+    // stealth mode was used) so the recipient can scan for this payment
+    let mut response = [0u8; 96];
     response[12..32].copy_from_slice(&first_cell);
+    response[32..].copy_from_slice(&ephemeral_point);
     api::return_value(uapi::ReturnFlags::empty(), &response);
 }
 
 fn handle_cell() {
     // get remaining hops with bounds check
     // prevents underflow if storage corrupted
+    // this is the cell's own just-instantiated storage, always warm
+    access_storage_key(&HOPS_REMAINING);
     let mut hops_storage = [0u8; 32];
     api::get_storage_or_zero(uapi::StorageFlags::empty(), &HOPS_REMAINING, &mut hops_storage);
     let remaining = hops_storage[0].min(HOP_COUNT);
-    
+
+    // gas parameters the router resolved (from its own configured-or-default
+    // values) and carried down into this cell's constructor, since this
+    // cell's own storage has no way to see the router's CFG_GAS_* slots
+    let deployment_gas = configured_deployment_gas();
+    let forward_gas = configured_forward_gas();
+    let max_gas_per_cell = configured_max_gas_per_cell();
+
     // get router address
     let mut router_storage = [0u8; 32];
     api::get_storage_or_zero(uapi::StorageFlags::empty(), &ROUTER_ADDRESS, &mut router_storage);
     let mut router = [0u8; 20];
     router.copy_from_slice(&router_storage[..20]);
     
-    // read destination
+    // read destination, plus the original sender and intent nonce riding
+    // along unchanged since route() first set them, needed
+    // here only if this turns out to be the final hop
+    let mut hop_data = [0u8; 48];
+    api::call_data_copy(&mut hop_data, 0);
     let mut destination = [0u8; 20];
-    api::call_data_copy(&mut destination, 0);
-    
+    destination.copy_from_slice(&hop_data[..20]);
+    let mut sender = [0u8; 20];
+    sender.copy_from_slice(&hop_data[20..40]);
+    let sender_nonce = u64::from_le_bytes(hop_data[40..48].try_into().unwrap());
+
     // get value to forward
     let mut value_bytes = [0u8; 32];
     api::value_transferred(&mut value_bytes);
-    
+    let value = u128::from_le_bytes(value_bytes[..16].try_into().unwrap());
+
     // check if final hop using saturating arithmetic
     // prevents underflow attacks
     let next_hop = remaining.saturating_sub(1);
-    
+
+    // EIP-2929 pre-flight check for the rest of the chain: a cell that
+    // can't possibly afford to finish forwarding terminates cleanly now
+    // instead of deploying (or delivering) into an out-of-gas failure.
+    // that's still a failed delivery from the sender's point of view, so
+    // it goes through the same observability + stuck-funds recovery path
+    // as a failed final hop instead of silently vanishing into the router
+    if api::gas_limit() < required_hop_gas(next_hop, &destination, deployment_gas, forward_gas) {
+        emit_delivery_failed(&destination, value);
+        let gas_for_credit = api::gas_limit().saturating_sub(forward_gas).min(max_gas_per_cell);
+        credit_stuck_funds_callback(&router, &sender, sender_nonce, value, gas_for_credit);
+        api::terminate(&router);
+    }
+
     if next_hop == 0 {
         // final hop - deliver to destination
+        let checkpoint = access_checkpoint();
+        access_account(&destination);
         let result = api::call(
             uapi::CallFlags::empty(),
             &destination,
-            api::gas_limit().saturating_sub(FORWARD_GAS).min(MAX_GAS_PER_CELL),
+            api::gas_limit().saturating_sub(forward_gas).min(max_gas_per_cell),
             0,
             &[0xff; 32],
             &value_bytes,
             &[],
             None,
         );
-        
+
         if result.is_err() {
-            // don't revert, just terminate - funds go to router
-            // ensures chain cleanup even on delivery failure
+            // don't revert, just terminate - funds go to router, but
+            // credit them to STUCK_FUNDS so the sender can reclaim them
+            // instead of them being silently lost
+            access_rollback(checkpoint);
+            emit_delivery_failed(&destination, value);
+
+            let gas_for_credit = api::gas_limit().saturating_sub(forward_gas).min(max_gas_per_cell);
+            credit_stuck_funds_callback(&router, &sender, sender_nonce, value, gas_for_credit);
+        } else {
+            emit_delivered(&destination, value);
         }
     } else {
         // create deterministic but unpredictable nonce
@@ -221,12 +1041,16 @@ fn handle_cell() {
         nonce_data[1..9].copy_from_slice(&api::ref_time_left().to_le_bytes());
         nonce_data[9..13].copy_from_slice(&(api::gas_price() as u32).to_le_bytes());
         let nonce = u64::from_le_bytes(nonce_data[..8].try_into().unwrap());
-        
-        // deploy next cell
-        let next_cell = deploy_cell(next_hop, router, nonce);
-        
+
+        // deploy next cell, carrying the same configured gas parameters
+        // down so the whole chain stays consistent even if the owner
+        // reconfigures mid-route
+        let next_cell = deploy_cell(next_hop, router, nonce, deployment_gas, forward_gas, max_gas_per_cell);
+
         // forward to next cell with calculated gas
-        let gas_for_next = api::gas_limit().saturating_sub(DEPLOYMENT_GAS).min(MAX_GAS_PER_CELL * next_hop as u64);
+        let checkpoint = access_checkpoint();
+        access_account(&next_cell);
+        let gas_for_next = api::gas_limit().saturating_sub(deployment_gas).min(max_gas_per_cell * next_hop as u64);
         let result = api::call(
             uapi::CallFlags::empty(),
             &next_cell,
@@ -234,13 +1058,22 @@ fn handle_cell() {
             0,
             &[0xff; 32],
             &value_bytes,
-            &destination[..],
+            &hop_data[..],
             None,
         );
-        
+
         if result.is_err() {
-            // don't revert, just terminate
-            // ensures cleanup continues even on forward failure
+            // don't revert, just terminate - and same as the final-hop and
+            // gas-shortfall failure paths, credit the value to STUCK_FUNDS
+            // instead of letting it vanish into the router silently (this
+            // can fail for reasons other than gas, e.g. a deeper
+            // deploy_cell() reverting, so it needs the same recovery path)
+            access_rollback(checkpoint);
+            emit_delivery_failed(&destination, value);
+            let gas_for_credit = api::gas_limit().saturating_sub(forward_gas).min(max_gas_per_cell);
+            credit_stuck_funds_callback(&router, &sender, sender_nonce, value, gas_for_credit);
+        } else {
+            emit_hop_forwarded(next_hop);
         }
     }
     
@@ -249,19 +1082,32 @@ fn handle_cell() {
     api::terminate(&router);
 }
 
-fn deploy_cell(hops: u8, router: [u8; 20], nonce: u64) -> [u8; 20] {
+fn deploy_cell(
+    hops: u8,
+    router: [u8; 20],
+    nonce: u64,
+    gas_deploy: u64,
+    gas_forward: u64,
+    gas_per_cell: u64,
+) -> [u8; 20] {
     // get own code hash
     let mut code_hash = [0u8; 32];
     api::own_code_hash(&mut code_hash);
-    
-    // constructor data
-    let mut constructor = [0u8; 22];
+
+    // constructor data - carries the caller's already-resolved configured
+    // gas parameters down to the new cell, since the new cell's own
+    // storage has no way to read the router's (or an upstream cell's)
+    // CFG_GAS_* slots directly
+    let mut constructor = [0u8; 46];
     constructor[0] = TYPE_CELL;
     constructor[1] = hops;
     constructor[2..22].copy_from_slice(&router);
-    
+    constructor[22..30].copy_from_slice(&gas_deploy.to_le_bytes());
+    constructor[30..38].copy_from_slice(&gas_forward.to_le_bytes());
+    constructor[38..46].copy_from_slice(&gas_per_cell.to_le_bytes());
+
     // prepare instantiate input
-    let mut input = [0u8; 54];
+    let mut input = [0u8; 78];
     input[..32].copy_from_slice(&code_hash);
     input[32..].copy_from_slice(&constructor);
     
@@ -283,14 +1129,23 @@ fn deploy_cell(hops: u8, router: [u8; 20], nonce: u64) -> [u8; 20] {
     let mut now = [0u8; 32];
     api::now(&mut now);
     salt_data[37..45].copy_from_slice(&now[..8]);
-    
+
+    // fold in the chain id (EIP-155 domain separation): without this, the
+    // same router nonce/block/timestamp on two parachains derive the same
+    // salt, so the exact same cell address is predictable and replayable
+    // cross-chain. chain_id is a little-endian U256, so its entropy is in
+    // [..8] - same slice route()'s intent check reads
+    let mut chain_id = [0u8; 32];
+    api::get_storage_or_zero(uapi::StorageFlags::empty(), &CHAIN_ID, &mut chain_id);
+    salt_data[45..53].copy_from_slice(&chain_id[..8]);
+
     // hash for final salt
     let mut salt = [0u8; 32];
-    api::hash_keccak_256(&salt_data[..45], &mut salt);
+    api::hash_keccak_256(&salt_data[..53], &mut salt);
     
     // deploy the cell with calculated gas limit
     let mut address = [0u8; 20];
-    let gas_for_deploy = api::gas_limit().saturating_sub(FORWARD_GAS).min(DEPLOYMENT_GAS);
+    let gas_for_deploy = api::gas_limit().saturating_sub(gas_forward).min(gas_deploy);
     let result = api::instantiate(
         gas_for_deploy,
         0,
@@ -310,17 +1165,8 @@ fn deploy_cell(hops: u8, router: [u8; 20], nonce: u64) -> [u8; 20] {
 }
 
 fn withdraw() {
-    // verify owner
-    let mut caller = [0u8; 20];
-    api::caller(&mut caller);
-    
-    let mut owner_storage = [0u8; 32];
-    api::get_storage_or_zero(uapi::StorageFlags::empty(), &OWNER, &mut owner_storage);
-    
-    if caller != owner_storage[..20] {
-        api::return_value(uapi::ReturnFlags::REVERT, b"not owner");
-    }
-    
+    let caller = require_owner();
+
     // get accumulated fees
     let mut fees_storage = [0u8; 32];
     api::get_storage_or_zero(uapi::StorageFlags::empty(), &FEES_COLLECTED, &mut fees_storage);